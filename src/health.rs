@@ -0,0 +1,73 @@
+//! Self-instrumentation for the telemetry pipeline itself.
+//!
+//! A failing agent connection or a saturated metrics queue today fails
+//! silently: traces and metrics just disappear. This module gives the
+//! pipeline a small set of its own namespaced counters so operators can see
+//! sideways degrading instead of inferring it from missing dashboards.
+
+/// A single health metric emitted about the telemetry pipeline.
+///
+/// Currently only counters are needed; this is an enum rather than a bare
+/// function so additional metric shapes (gauges, timers) can be added
+/// without changing every call site.
+#[derive(Debug, Clone, Copy)]
+pub enum HealthMetric {
+    Count(&'static str, i64),
+}
+
+/// Metric names emitted by [`record`].
+pub mod names {
+    /// A trace stats payload was posted to the trace agent (any HTTP
+    /// response counts). Successful span exports are not counted.
+    pub const SEND_TRACES: &str = "sideways.send.traces";
+    /// OpenTelemetry's global error handler reported an error (e.g. a failed
+    /// span export), or a trace stats payload could not be posted.
+    pub const SEND_TRACES_ERRORS: &str = "sideways.send.traces.errors";
+    /// A metric point was accepted by the sink.
+    pub const METRICS_POINTS: &str = "sideways.metrics.points";
+    /// The socket sink failed to send a metric point. Queue overflow is not
+    /// counted; the queue is unbounded.
+    pub const METRICS_DROPPED: &str = "sideways.metrics.dropped";
+}
+
+std::thread_local! {
+    /// Guards against re-entering `record` on the same thread from within
+    /// the emit it triggers, as happens when the global client's sink calls
+    /// `record` synchronously: `record` -> `statsd_count!` -> sink `emit` ->
+    /// `record` -> ...
+    ///
+    /// With the queuing sink built by `metrics::init_metrics`, the emit that
+    /// calls `record` runs on the queue's worker thread, so this guard does
+    /// not see it; `metrics::HealthMetricSink` breaks that loop instead by
+    /// not counting the health counters themselves.
+    static RECORDING: std::cell::Cell<bool> = const { std::cell::Cell::new(false) };
+}
+
+/// Record a health metric through the existing cadence global client.
+///
+/// This is a no-op if metrics haven't been initialized, since
+/// `cadence_macros` silently drops emissions without a global client
+/// registered. It is also a no-op when called re-entrantly on the same
+/// thread from within the `emit` this call itself triggers (see
+/// `RECORDING`).
+pub fn record(metric: HealthMetric) {
+    if !cadence_macros::is_global_default_set() {
+        return;
+    }
+    if RECORDING.with(|recording| recording.replace(true)) {
+        return;
+    }
+    struct ResetOnDrop;
+    impl Drop for ResetOnDrop {
+        fn drop(&mut self) {
+            RECORDING.with(|recording| recording.set(false));
+        }
+    }
+    let _reset = ResetOnDrop;
+
+    match metric {
+        HealthMetric::Count(name, value) => {
+            cadence_macros::statsd_count!(name, value);
+        }
+    }
+}
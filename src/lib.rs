@@ -32,15 +32,15 @@
 //!     // Emit metrics using macros - no need to import cadence!
 //!     statsd_count!("requests.handled", 1, "status" => "success");
 //!
-//!     // Cleanup on shutdown
-//!     if let Some(tracer) = telemetry.tracer_provider {
-//!         let _ = tracer.shutdown();
-//!     }
+//!     // Cleanup on shutdown: flushes the tracer and drains queued metrics
+//!     telemetry.shutdown();
 //! }
 //! ```
 
+pub mod health;
 pub mod metrics;
 pub mod prelude;
+pub mod stats;
 pub mod tracing;
 
 // Re-export cadence and cadence-macros for advanced usage
@@ -49,6 +49,7 @@ pub use cadence_macros;
 
 use std::env;
 use thiserror::Error;
+use tracing_subscriber::EnvFilter;
 
 #[derive(Debug, Error)]
 pub enum TelemetryError {
@@ -77,10 +78,15 @@ pub struct TelemetryConfig {
     pub dd_service: String,
     /// Datadog environment
     pub dd_env: String,
+    /// Application version reported to Datadog (e.g. a release tag or git
+    /// SHA), distinct from `dd_service`
+    pub dd_version: String,
     /// Datadog trace agent URL
     pub dd_trace_agent_url: String,
     /// RUST_LOG filter
     pub rust_log: String,
+    /// Which backend to export spans to, when tracing is enabled
+    pub trace_exporter: TraceExporter,
 
     /// Enable/disable metrics (default: true)
     pub metrics_enabled: bool,
@@ -90,6 +96,76 @@ pub struct TelemetryConfig {
     pub statsd_port: u16,
     /// Metrics prefix/namespace
     pub metrics_prefix: String,
+    /// Transport used to reach the StatsD/DogStatsD agent
+    pub statsd_transport: StatsdTransport,
+
+    /// Directory to write rotating log files into. When `None` (the
+    /// default), file logging is disabled and only console output is
+    /// produced.
+    pub log_dir: Option<String>,
+    /// Prefix for rotated log file names (e.g. the service name)
+    pub log_filename_prefix: String,
+    /// Suffix for rotated log file names (e.g. `log` so editors recognize
+    /// the file as a log)
+    pub log_filename_suffix: String,
+    /// How often the log file should roll over
+    pub log_rotation: LogRotation,
+
+    /// Enable/disable client-side APM trace stats aggregation (default: true)
+    pub trace_stats_enabled: bool,
+    /// Width of a trace stats aggregation window, in seconds
+    pub trace_stats_bucket_secs: u64,
+}
+
+/// Trace export backend.
+///
+/// `Datadog` talks to a local Datadog trace agent via dd-trace-rs. `Otlp`
+/// frees sideways from requiring that agent by exporting directly to an
+/// arbitrary OpenTelemetry collector instead.
+#[derive(Debug, Clone, PartialEq)]
+pub enum TraceExporter {
+    Datadog,
+    Otlp {
+        protocol: OtlpProtocol,
+        /// Collector endpoint. When `None`, the OTLP exporter falls back to
+        /// `OTEL_EXPORTER_OTLP_ENDPOINT` / its own protocol default.
+        endpoint: Option<String>,
+    },
+}
+
+impl Default for TraceExporter {
+    fn default() -> Self {
+        TraceExporter::Datadog
+    }
+}
+
+/// Wire protocol used by the OTLP trace exporter.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OtlpProtocol {
+    #[default]
+    Grpc,
+    Http,
+}
+
+/// Transport used to reach the StatsD/DogStatsD agent.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StatsdTransport {
+    /// Send metrics over a UDP socket (the default, works with any StatsD
+    /// agent listening on a host/port).
+    Udp { host: String, port: u16 },
+    /// Send metrics over a Unix domain datagram socket, the standard
+    /// deployment for a DogStatsD agent co-located on the same host.
+    Uds { path: String },
+}
+
+/// Rotation policy for the rolling file log appender.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LogRotation {
+    Minutely,
+    Hourly,
+    #[default]
+    Daily,
+    Never,
 }
 
 impl Default for TelemetryConfig {
@@ -98,12 +174,24 @@ impl Default for TelemetryConfig {
             datadog_enabled: true,
             dd_service: "sideways-service".to_string(),
             dd_env: "development".to_string(),
+            dd_version: String::new(),
             dd_trace_agent_url: "http://localhost:8126".to_string(),
             rust_log: "info".to_string(),
+            trace_exporter: TraceExporter::default(),
             metrics_enabled: true,
             statsd_host: "localhost".to_string(),
             statsd_port: 8125,
             metrics_prefix: "sideways".to_string(),
+            statsd_transport: StatsdTransport::Udp {
+                host: "localhost".to_string(),
+                port: 8125,
+            },
+            log_dir: None,
+            log_filename_prefix: String::new(),
+            log_filename_suffix: String::new(),
+            log_rotation: LogRotation::default(),
+            trace_stats_enabled: true,
+            trace_stats_bucket_secs: stats::DEFAULT_BUCKET_SECS,
         }
     }
 }
@@ -134,10 +222,27 @@ impl TelemetryConfig {
         if let Ok(dd_env) = env::var("DD_ENV") {
             config.dd_env = dd_env;
         }
+        if let Ok(version) = env::var("DD_VERSION") {
+            config.dd_version = version;
+        }
         if let Ok(url) = env::var("DD_TRACE_AGENT_URL") {
             config.dd_trace_agent_url = url;
         }
 
+        // Trace exporter selection
+        if let Ok(exporter) = env::var("DD_TRACE_EXPORTER") {
+            if exporter.to_lowercase() == "otlp" {
+                let protocol = match env::var("OTEL_EXPORTER_OTLP_PROTOCOL") {
+                    Ok(p) if p.to_lowercase().contains("http") => OtlpProtocol::Http,
+                    _ => OtlpProtocol::Grpc,
+                };
+                config.trace_exporter = TraceExporter::Otlp {
+                    protocol,
+                    endpoint: env::var("OTEL_EXPORTER_OTLP_ENDPOINT").ok(),
+                };
+            }
+        }
+
         // Logging configuration
         if let Ok(rust_log) = env::var("RUST_LOG") {
             config.rust_log = rust_log;
@@ -156,6 +261,44 @@ impl TelemetryConfig {
             config.metrics_prefix = prefix;
         }
 
+        // An explicit socket path takes precedence over the UDP host/port
+        // above; `init_metrics` falls back to UDP if the socket doesn't exist.
+        if let Ok(path) = env::var("STATSD_SOCKET_PATH") {
+            config.statsd_transport = StatsdTransport::Uds { path };
+        }
+
+        // File logging configuration
+        if let Ok(dir) = env::var("LOG_DIR") {
+            config.log_dir = Some(dir);
+        }
+        if let Ok(prefix) = env::var("LOG_FILENAME_PREFIX") {
+            config.log_filename_prefix = prefix;
+        }
+        if let Ok(suffix) = env::var("LOG_FILENAME_SUFFIX") {
+            config.log_filename_suffix = suffix;
+        }
+        if let Ok(rotation) = env::var("LOG_ROTATION") {
+            config.log_rotation = match rotation.to_lowercase().as_str() {
+                "minutely" => LogRotation::Minutely,
+                "hourly" => LogRotation::Hourly,
+                "daily" => LogRotation::Daily,
+                "never" => LogRotation::Never,
+                _ => config.log_rotation,
+            };
+        }
+
+        // Trace stats configuration
+        if let Ok(enabled) = env::var("DD_TRACE_STATS_ENABLED") {
+            if enabled.to_lowercase() == "false" {
+                config.trace_stats_enabled = false;
+            }
+        }
+        if let Ok(secs) = env::var("DD_TRACE_STATS_BUCKET_SECS") {
+            if let Ok(secs) = secs.parse() {
+                config.trace_stats_bucket_secs = secs;
+            }
+        }
+
         config
     }
 
@@ -187,6 +330,11 @@ impl TelemetryConfigBuilder {
         self
     }
 
+    pub fn dd_version(mut self, version: impl Into<String>) -> Self {
+        self.config.dd_version = version.into();
+        self
+    }
+
     pub fn dd_trace_agent_url(mut self, url: impl Into<String>) -> Self {
         self.config.dd_trace_agent_url = url.into();
         self
@@ -197,6 +345,11 @@ impl TelemetryConfigBuilder {
         self
     }
 
+    pub fn trace_exporter(mut self, exporter: TraceExporter) -> Self {
+        self.config.trace_exporter = exporter;
+        self
+    }
+
     pub fn metrics_enabled(mut self, enabled: bool) -> Self {
         self.config.metrics_enabled = enabled;
         self
@@ -217,15 +370,136 @@ impl TelemetryConfigBuilder {
         self
     }
 
+    pub fn statsd_transport(mut self, transport: StatsdTransport) -> Self {
+        self.config.statsd_transport = transport;
+        self
+    }
+
+    pub fn statsd_socket_path(mut self, path: impl Into<String>) -> Self {
+        self.config.statsd_transport = StatsdTransport::Uds { path: path.into() };
+        self
+    }
+
+    pub fn log_dir(mut self, dir: impl Into<String>) -> Self {
+        self.config.log_dir = Some(dir.into());
+        self
+    }
+
+    pub fn log_filename_prefix(mut self, prefix: impl Into<String>) -> Self {
+        self.config.log_filename_prefix = prefix.into();
+        self
+    }
+
+    pub fn log_filename_suffix(mut self, suffix: impl Into<String>) -> Self {
+        self.config.log_filename_suffix = suffix.into();
+        self
+    }
+
+    pub fn log_rotation(mut self, rotation: LogRotation) -> Self {
+        self.config.log_rotation = rotation;
+        self
+    }
+
+    pub fn trace_stats_enabled(mut self, enabled: bool) -> Self {
+        self.config.trace_stats_enabled = enabled;
+        self
+    }
+
+    pub fn trace_stats_bucket_secs(mut self, secs: u64) -> Self {
+        self.config.trace_stats_bucket_secs = secs;
+        self
+    }
+
     pub fn build(self) -> TelemetryConfig {
         self.config
     }
 }
 
+/// Default bound on how long `Telemetry::shutdown` waits for the metrics
+/// queue to drain.
+const DEFAULT_SHUTDOWN_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(2);
+
 /// Telemetry components that need to be kept alive
 pub struct Telemetry {
     /// Datadog tracer provider (must be kept alive and shutdown on exit)
     pub tracer_provider: Option<opentelemetry_sdk::trace::SdkTracerProvider>,
+    /// Guard for the non-blocking file log writer. Dropping this flushes and
+    /// stops the background writer thread, so it must be kept alive for the
+    /// duration of the program.
+    pub log_guard: Option<tracing_appender::non_blocking::WorkerGuard>,
+    /// Handle to live-reload the console layer's log filter, used by
+    /// `Telemetry::set_log_filter`.
+    pub log_filter_handle: Option<tracing::LogFilterHandle>,
+    /// StatsD client, kept alive so `Telemetry::shutdown` can give the
+    /// queuing sink a chance to drain before the process exits.
+    pub metrics_client: Option<cadence::StatsdClient>,
+    /// Shared handle to the metrics sink, so `Telemetry::shutdown` can flush
+    /// it directly instead of just waiting on the queue worker.
+    pub metrics_sink: Option<std::sync::Arc<dyn cadence::MetricSink + Send + Sync>>,
+}
+
+impl Telemetry {
+    /// Replace the console layer's log filter with new `RUST_LOG`-style
+    /// directives (e.g. `"sideways=debug,hyper=info"`), without restarting
+    /// the process.
+    pub fn set_log_filter(&self, directives: &str) -> Result<(), TelemetryError> {
+        let handle = self.log_filter_handle.as_ref().ok_or_else(|| {
+            TelemetryError::SubscriberInit("no reloadable log filter installed".to_string())
+        })?;
+
+        let filter = EnvFilter::try_new(directives)
+            .map_err(|e| TelemetryError::SubscriberInit(format!("invalid filter directives: {}", e)))?;
+
+        handle
+            .reload(filter)
+            .map_err(|e| TelemetryError::SubscriberInit(format!("failed to reload log filter: {}", e)))
+    }
+
+    /// Flush the tracer provider and the metrics sink, bounding how long we
+    /// wait on the latter so the documented shutdown sequence actually
+    /// delivers the last metrics rather than racing process exit.
+    ///
+    /// Uses a default 2 second bound; use [`Telemetry::shutdown_with_timeout`]
+    /// to customize it.
+    pub fn shutdown(&self) {
+        self.shutdown_with_timeout(DEFAULT_SHUTDOWN_TIMEOUT);
+    }
+
+    /// Like [`Telemetry::shutdown`], but with an explicit bound on how long
+    /// to wait for the metrics sink to flush.
+    pub fn shutdown_with_timeout(&self, timeout: std::time::Duration) {
+        if let Some(tracer) = &self.tracer_provider {
+            if let Err(e) = tracer.shutdown() {
+                eprintln!("⚠️  Sideways Telemetry: failed to shut down tracer provider: {}", e);
+            }
+        }
+
+        if let Some(sink) = &self.metrics_sink {
+            eprintln!(
+                "📊 Sideways Telemetry: flushing metrics sink (up to {:?})...",
+                timeout
+            );
+
+            // `flush` drains the queuing sink's worker and the buffered
+            // sink's pending bytes synchronously, but isn't guaranteed to
+            // return promptly, so bound the wait rather than blocking
+            // shutdown indefinitely.
+            let (tx, rx) = std::sync::mpsc::channel();
+            let sink = std::sync::Arc::clone(sink);
+            std::thread::spawn(move || {
+                let _ = tx.send(sink.flush());
+            });
+
+            match rx.recv_timeout(timeout) {
+                Ok(Ok(())) => {}
+                Ok(Err(e)) => eprintln!("⚠️  Sideways Telemetry: failed to flush metrics: {}", e),
+                Err(_) => eprintln!(
+                    "⚠️  Sideways Telemetry: metrics flush timed out after {:?}",
+                    timeout
+                ),
+            }
+        }
+    }
 }
 
 /// Initialize telemetry with the given configuration.
@@ -240,32 +514,47 @@ pub struct Telemetry {
 pub async fn init_telemetry(config: TelemetryConfig) -> Telemetry {
     eprintln!("🦀 Sideways Telemetry: Initializing...");
 
-    // Initialize Datadog tracing
-    let tracer_provider = if config.datadog_enabled {
-        match tracing::init_datadog(&config) {
-            Ok(provider) => {
-                eprintln!("✅ Sideways Telemetry: Datadog tracing initialized");
-                Some(provider)
+    // Initialize tracing (Datadog agent or OTLP collector, per config.trace_exporter)
+    let (tracer_provider, log_guard, log_filter_handle) = if config.datadog_enabled {
+        let init_result = match &config.trace_exporter {
+            TraceExporter::Datadog => tracing::init_datadog(&config),
+            TraceExporter::Otlp { .. } => tracing::init_otlp(&config),
+        };
+        match init_result {
+            Ok((provider, log_guard, filter_handle)) => {
+                eprintln!("✅ Sideways Telemetry: tracing initialized ({:?})", config.trace_exporter);
+                (Some(provider), log_guard, Some(filter_handle))
             }
             Err(err) => {
-                eprintln!("⚠️  Sideways Telemetry: Datadog tracing unavailable: {}", err);
-                None
+                eprintln!("⚠️  Sideways Telemetry: tracing unavailable: {}", err);
+                (None, None, None)
             }
         }
     } else {
         eprintln!("📊 Sideways Telemetry: Datadog tracing disabled");
-        tracing::init_console_logging(&config);
-        None
+        let (log_guard, filter_handle) = tracing::init_console_logging(&config);
+        (None, log_guard, Some(filter_handle))
     };
 
     // Initialize metrics
-    if config.metrics_enabled {
-        if let Err(err) = metrics::init_metrics(&config) {
-            eprintln!("⚠️  Sideways Telemetry: Metrics unavailable: {}", err);
+    let (metrics_client, metrics_sink) = if config.metrics_enabled {
+        match metrics::init_metrics(&config) {
+            Ok((client, sink)) => (Some(client), Some(sink)),
+            Err(err) => {
+                eprintln!("⚠️  Sideways Telemetry: Metrics unavailable: {}", err);
+                (None, None)
+            }
         }
     } else {
         eprintln!("📊 Sideways Telemetry: Metrics disabled");
-    }
+        (None, None)
+    };
 
-    Telemetry { tracer_provider }
+    Telemetry {
+        tracer_provider,
+        log_guard,
+        log_filter_handle,
+        metrics_client,
+        metrics_sink,
+    }
 }
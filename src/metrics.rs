@@ -1,20 +1,147 @@
-use crate::{TelemetryConfig, TelemetryError};
-use cadence::{BufferedUdpMetricSink, QueuingMetricSink, StatsdClient};
+use crate::health::{self, HealthMetric};
+use crate::{StatsdTransport, TelemetryConfig, TelemetryError};
+use cadence::{BufferedUdpMetricSink, MetricSink, QueuingMetricSink, StatsdClient};
+use std::io;
 use std::net::UdpSocket;
+use std::os::unix::net::UnixDatagram;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+/// Matches cadence's own UDP buffer sizing, which targets a single
+/// unfragmented Ethernet frame.
+const DEFAULT_BUFFER_SIZE: usize = 512;
+
+/// Buffered `MetricSink` over a Unix datagram socket, for a DogStatsD agent
+/// exposed over a UDS endpoint rather than UDP. Mirrors the batching
+/// cadence's own `BufferedUdpMetricSink` does: metrics are newline-joined
+/// into a buffer and flushed as one datagram once it's full.
+struct BufferedUnixMetricSink {
+    socket: UnixDatagram,
+    buffer: Mutex<Vec<u8>>,
+}
+
+impl BufferedUnixMetricSink {
+    fn connect(path: &str) -> io::Result<Self> {
+        let socket = UnixDatagram::unbound()?;
+        socket.connect(path)?;
+        Ok(Self {
+            socket,
+            buffer: Mutex::new(Vec::with_capacity(DEFAULT_BUFFER_SIZE)),
+        })
+    }
+}
+
+impl MetricSink for BufferedUnixMetricSink {
+    fn emit(&self, metric: &str) -> io::Result<usize> {
+        let mut buffer = self.buffer.lock().unwrap();
+
+        if !buffer.is_empty() && buffer.len() + 1 + metric.len() > DEFAULT_BUFFER_SIZE {
+            self.socket.send(&buffer)?;
+            buffer.clear();
+        }
+
+        if !buffer.is_empty() {
+            buffer.push(b'\n');
+        }
+        buffer.extend_from_slice(metric.as_bytes());
+
+        if buffer.len() >= DEFAULT_BUFFER_SIZE {
+            self.socket.send(&buffer)?;
+            buffer.clear();
+        }
+
+        Ok(metric.len())
+    }
+
+    fn flush(&self) -> io::Result<()> {
+        let mut buffer = self.buffer.lock().unwrap();
+        if !buffer.is_empty() {
+            self.socket.send(&buffer)?;
+            buffer.clear();
+        }
+        Ok(())
+    }
+}
+
+/// Wraps a `MetricSink` to report [`health`] counters for accepted and
+/// dropped metric points.
+///
+/// Must wrap the *inner* sink (the one that actually talks to the socket),
+/// not the `QueuingMetricSink` - `QueuingMetricSink::emit` only enqueues and
+/// always returns `Ok`, with the real send happening (and any drop
+/// decided) on its background worker thread. Wrapping it from the outside
+/// would mean this sink never observes an actual failure.
+struct HealthMetricSink<S> {
+    inner: S,
+}
+
+/// Forwards to a shared `MetricSink`, so the same sink can be handed to the
+/// `StatsdClient` (for emitting) and retained by the caller (for flushing on
+/// shutdown) without requiring ownership of two copies.
+struct SharedSink(Arc<dyn MetricSink + Send + Sync>);
+
+impl MetricSink for SharedSink {
+    fn emit(&self, metric: &str) -> io::Result<usize> {
+        self.0.emit(metric)
+    }
+
+    fn flush(&self) -> io::Result<()> {
+        self.0.flush()
+    }
+}
+
+impl<S: MetricSink> MetricSink for HealthMetricSink<S> {
+    fn emit(&self, metric: &str) -> std::io::Result<usize> {
+        // Health counters reach this sink too (on the queue's worker
+        // thread). Counting them would enqueue a new point for every point
+        // sent, so they are passed through uncounted.
+        let name = metric.split(':').next().unwrap_or(metric);
+        let is_health_metric = [
+            health::names::SEND_TRACES,
+            health::names::SEND_TRACES_ERRORS,
+            health::names::METRICS_POINTS,
+            health::names::METRICS_DROPPED,
+        ]
+        .iter()
+        .any(|health_name| name.ends_with(health_name));
+        if is_health_metric {
+            return self.inner.emit(metric);
+        }
+
+        match self.inner.emit(metric) {
+            Ok(written) => {
+                health::record(HealthMetric::Count(health::names::METRICS_POINTS, 1));
+                Ok(written)
+            }
+            Err(e) => {
+                health::record(HealthMetric::Count(health::names::METRICS_DROPPED, 1));
+                Err(e)
+            }
+        }
+    }
+
+    fn flush(&self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
 
 /// Initialize the Cadence StatsD metrics client.
 ///
 /// This function sets up a production-grade metrics client with:
-/// - UDP socket for low-overhead transmission
+/// - UDP or Unix domain socket transport, depending on `config.statsd_transport`
 /// - Buffered sink for efficient batching
 /// - Queuing sink for asynchronous dispatch
 ///
-/// The client is registered globally for use with cadence-macros.
+/// The client is registered globally for use with cadence-macros. Also
+/// returned is a shared handle to the underlying sink, which the caller
+/// (`init_telemetry`) keeps so `Telemetry::shutdown` can flush buffered
+/// points (e.g. `BufferedUnixMetricSink::buffer`) on exit.
 ///
 /// # Returns
 ///
-/// Returns `Ok(())` if metrics are successfully initialized, or an error if:
-/// - UDP socket binding fails
+/// Returns the `StatsdClient` and a shared sink handle if metrics are
+/// successfully initialized, or an error if:
+/// - Socket binding/connecting fails
 /// - Metric sink creation fails
 ///
 /// # Example Usage
@@ -35,7 +162,9 @@ use std::net::UdpSocket;
 /// statsd_distribution!("some.distribution", 1.0, "tag" => "val");
 /// statsd_set!("some.set", 1, "tag" => "val");
 /// ```
-pub fn init_metrics(config: &TelemetryConfig) -> Result<(), TelemetryError> {
+pub fn init_metrics(
+    config: &TelemetryConfig,
+) -> Result<(StatsdClient, Arc<dyn MetricSink + Send + Sync>), TelemetryError> {
     let tags_info = if config.global_tags.is_empty() {
         String::new()
     } else {
@@ -50,24 +179,47 @@ pub fn init_metrics(config: &TelemetryConfig) -> Result<(), TelemetryError> {
         )
     };
 
-    eprintln!(
-        "📊 Initializing metrics: {}:{} with prefix '{}'{}",
-        config.statsd_host, config.statsd_port, config.metrics_prefix, tags_info
-    );
+    // Prefer a configured UDS socket, but only if it actually exists -
+    // otherwise fall back to UDP rather than silently dropping every metric.
+    let use_uds = match &config.statsd_transport {
+        StatsdTransport::Uds { path } if Path::new(path).exists() => Some(path.clone()),
+        StatsdTransport::Uds { path } => {
+            eprintln!(
+                "⚠️  STATSD_SOCKET_PATH {} does not exist, falling back to UDP {}:{}",
+                path, config.statsd_host, config.statsd_port
+            );
+            None
+        }
+        StatsdTransport::Udp { .. } => None,
+    };
+
+    let sink: Arc<dyn MetricSink + Send + Sync> = if let Some(path) = use_uds {
+        eprintln!(
+            "📊 Initializing metrics: unix socket {} with prefix '{}'{}",
+            path, config.metrics_prefix, tags_info
+        );
+
+        let uds = BufferedUnixMetricSink::connect(&path).map_err(TelemetryError::SocketBind)?;
+        Arc::new(QueuingMetricSink::from(HealthMetricSink { inner: uds }))
+    } else {
+        eprintln!(
+            "📊 Initializing metrics: {}:{} with prefix '{}'{}",
+            config.statsd_host, config.statsd_port, config.metrics_prefix, tags_info
+        );
 
-    // Bind to an ephemeral UDP port
-    let socket = UdpSocket::bind("0.0.0.0:0").map_err(TelemetryError::SocketBind)?;
+        // Bind to an ephemeral UDP port
+        let socket = UdpSocket::bind("0.0.0.0:0").map_err(TelemetryError::SocketBind)?;
 
-    // Create buffered UDP sink for efficient transmission
-    let buffered =
-        BufferedUdpMetricSink::from((&config.statsd_host[..], config.statsd_port), socket)
-            .map_err(TelemetryError::SinkCreation)?;
+        // Create buffered UDP sink for efficient transmission
+        let buffered =
+            BufferedUdpMetricSink::from((&config.statsd_host[..], config.statsd_port), socket)
+                .map_err(TelemetryError::SinkCreation)?;
 
-    // Add queuing layer for asynchronous dispatch
-    let queued = QueuingMetricSink::from(buffered);
+        Arc::new(QueuingMetricSink::from(HealthMetricSink { inner: buffered }))
+    };
 
     // Create client with namespace prefix and global tags using builder pattern
-    let mut builder = StatsdClient::builder(&config.metrics_prefix, queued);
+    let mut builder = StatsdClient::builder(&config.metrics_prefix, SharedSink(Arc::clone(&sink)));
 
     // Add each global tag to the client
     for (key, value) in &config.global_tags {
@@ -76,10 +228,11 @@ pub fn init_metrics(config: &TelemetryConfig) -> Result<(), TelemetryError> {
 
     let client = builder.build();
 
-    // Register as global default for macro usage
-    cadence_macros::set_global_default(client);
+    // Register a clone as global default for macro usage; the original is
+    // returned so it can be kept alive for a graceful shutdown flush.
+    cadence_macros::set_global_default(client.clone());
 
     eprintln!("✅ Metrics initialized successfully");
 
-    Ok(())
+    Ok((client, sink))
 }
@@ -0,0 +1,413 @@
+//! Client-side APM trace statistics.
+//!
+//! `/v0.6/stats` payloads keep Datadog dashboards accurate even when traces
+//! are sampled out, because the aggregates below are computed from every
+//! span that passes through the process, not just the ones that get kept.
+//! This module taps the `tracing-opentelemetry` span pipeline via a
+//! dedicated [`Layer`], buckets spans into fixed time windows on close, and
+//! runs a background thread that flushes closed windows to the trace agent.
+
+use crate::health::{self, HealthMetric};
+use crate::TelemetryConfig;
+use datadog_ddsketch::{Config as SketchConfig, DDSketch};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tracing::span;
+use tracing_subscriber::layer::Context as LayerContext;
+use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::Layer;
+
+/// Relative accuracy requested of every duration sketch (~0.775%).
+const SKETCH_RELATIVE_ACCURACY: f64 = 0.00775;
+
+/// Default width of an aggregation window, in seconds.
+pub const DEFAULT_BUCKET_SECS: u64 = 10;
+
+/// Identifies one aggregation bucket within a window.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct BucketKey {
+    service: String,
+    name: String,
+    resource: String,
+    span_type: String,
+    http_status_code: u32,
+    synthetics: bool,
+}
+
+/// Running totals for a single `BucketKey` within a single window.
+struct BucketStats {
+    hits: u64,
+    top_level_hits: u64,
+    errors: u64,
+    duration_nanos: u64,
+    duration_sketch: DDSketch,
+    error_duration_sketch: DDSketch,
+}
+
+impl BucketStats {
+    fn new() -> Self {
+        let config = SketchConfig::defaults(SKETCH_RELATIVE_ACCURACY);
+        Self {
+            hits: 0,
+            top_level_hits: 0,
+            errors: 0,
+            duration_nanos: 0,
+            duration_sketch: DDSketch::new(config),
+            error_duration_sketch: DDSketch::new(config),
+        }
+    }
+
+    fn record(&mut self, duration_nanos: u64, top_level: bool, error: bool) {
+        self.hits += 1;
+        self.duration_nanos += duration_nanos;
+        self.duration_sketch.add(duration_nanos as f64);
+        if top_level {
+            self.top_level_hits += 1;
+        }
+        if error {
+            self.errors += 1;
+            self.error_duration_sketch.add(duration_nanos as f64);
+        }
+    }
+}
+
+/// Buckets grouped by time window, keyed by window start (unix seconds,
+/// truncated to the bucket interval).
+type Windows = HashMap<u64, HashMap<BucketKey, BucketStats>>;
+
+/// Shared state between the tracing layer (producer) and the flush thread
+/// (consumer).
+#[derive(Default)]
+struct Aggregator {
+    windows: Windows,
+}
+
+/// Marks when a span started, so `on_close` can compute its duration.
+struct SpanStart(Instant);
+
+/// Extra fields pulled off a span for bucketing. All fields fall back to
+/// sensible defaults when the underlying instrumentation doesn't set them.
+#[derive(Default)]
+struct SpanFields {
+    resource: Option<String>,
+    span_type: Option<String>,
+    http_status_code: u32,
+    synthetics: bool,
+    error: bool,
+}
+
+/// `tracing_subscriber::Layer` that buckets spans into APM trace stats on
+/// close. Durations are measured locally rather than read back from the
+/// OpenTelemetry span data so stats keep working even if the span is
+/// ultimately dropped by sampling.
+pub struct TraceStatsLayer {
+    aggregator: Arc<Mutex<Aggregator>>,
+    service: String,
+    bucket_secs: u64,
+}
+
+impl TraceStatsLayer {
+    fn new(service: String, bucket_secs: u64) -> Self {
+        Self {
+            aggregator: Arc::new(Mutex::new(Aggregator::default())),
+            service,
+            bucket_secs,
+        }
+    }
+}
+
+/// Extracts the `SpanFields` cadence/hits-bucketing attributes off a span's
+/// recorded fields. Unrecognized fields are ignored, matching how
+/// `tracing`'s own `fmt` layer treats unknown fields.
+struct SpanFieldsVisitor<'a> {
+    fields: &'a mut SpanFields,
+}
+
+impl tracing::field::Visit for SpanFieldsVisitor<'_> {
+    fn record_str(&mut self, field: &tracing::field::Field, value: &str) {
+        match field.name() {
+            "resource" => self.fields.resource = Some(value.to_string()),
+            "span.type" | "span_type" => self.fields.span_type = Some(value.to_string()),
+            _ => {}
+        }
+    }
+
+    fn record_bool(&mut self, field: &tracing::field::Field, value: bool) {
+        match field.name() {
+            "synthetics" => self.fields.synthetics = value,
+            "error" => self.fields.error = value,
+            _ => {}
+        }
+    }
+
+    fn record_u64(&mut self, field: &tracing::field::Field, value: u64) {
+        if field.name() == "http.status_code" || field.name() == "http_status_code" {
+            self.fields.http_status_code = value as u32;
+        }
+    }
+
+    fn record_i64(&mut self, field: &tracing::field::Field, value: i64) {
+        if field.name() == "http.status_code" || field.name() == "http_status_code" {
+            self.fields.http_status_code = value.max(0) as u32;
+        }
+    }
+
+    fn record_debug(&mut self, field: &tracing::field::Field, value: &dyn std::fmt::Debug) {
+        match field.name() {
+            "resource" => self.fields.resource = Some(format!("{:?}", value)),
+            "span.type" | "span_type" => self.fields.span_type = Some(format!("{:?}", value)),
+            "error" => self.fields.error = true,
+            _ => {}
+        }
+    }
+}
+
+impl<S> Layer<S> for TraceStatsLayer
+where
+    S: tracing::Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_new_span(&self, attrs: &span::Attributes<'_>, id: &span::Id, ctx: LayerContext<'_, S>) {
+        if let Some(span) = ctx.span(id) {
+            let mut fields = SpanFields::default();
+            attrs.record(&mut SpanFieldsVisitor { fields: &mut fields });
+
+            let mut extensions = span.extensions_mut();
+            extensions.insert(SpanStart(Instant::now()));
+            extensions.insert(fields);
+        }
+    }
+
+    fn on_record(&self, id: &span::Id, values: &span::Record<'_>, ctx: LayerContext<'_, S>) {
+        if let Some(span) = ctx.span(id) {
+            let mut extensions = span.extensions_mut();
+            if let Some(fields) = extensions.get_mut::<SpanFields>() {
+                values.record(&mut SpanFieldsVisitor { fields });
+            }
+        }
+    }
+
+    fn on_close(&self, id: span::Id, ctx: LayerContext<'_, S>) {
+        let Some(span) = ctx.span(&id) else {
+            return;
+        };
+
+        let duration_nanos = {
+            let extensions = span.extensions();
+            match extensions.get::<SpanStart>() {
+                Some(SpanStart(start)) => start.elapsed().as_nanos() as u64,
+                None => return,
+            }
+        };
+
+        let fields = span
+            .extensions()
+            .get::<SpanFields>()
+            .map(|f| SpanFields {
+                resource: f.resource.clone(),
+                span_type: f.span_type.clone(),
+                http_status_code: f.http_status_code,
+                synthetics: f.synthetics,
+                error: f.error,
+            })
+            .unwrap_or_default();
+
+        let meta = span.metadata();
+        let key = BucketKey {
+            service: self.service.clone(),
+            name: meta.name().to_string(),
+            resource: fields.resource.unwrap_or_else(|| meta.name().to_string()),
+            span_type: fields.span_type.unwrap_or_else(|| "custom".to_string()),
+            http_status_code: fields.http_status_code,
+            synthetics: fields.synthetics,
+        };
+        let top_level = span.parent().is_none();
+
+        let window_start = window_start_secs(self.bucket_secs);
+        let mut aggregator = self.aggregator.lock().unwrap();
+        aggregator
+            .windows
+            .entry(window_start)
+            .or_default()
+            .entry(key)
+            .or_insert_with(BucketStats::new)
+            .record(duration_nanos, top_level, fields.error);
+    }
+}
+
+fn window_start_secs(bucket_secs: u64) -> u64 {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    now - (now % bucket_secs.max(1))
+}
+
+/// One group of aggregated stats within a window, matching the agent's
+/// `ClientGroupedStats`. Field names must match the agent's msgp decoder
+/// exactly (it decodes by PascalCase string tag, not by position), or the
+/// agent silently stores zeroed/empty buckets.
+#[derive(Serialize)]
+struct ClientStatsBucket {
+    #[serde(rename = "Name")]
+    name: String,
+    #[serde(rename = "Service")]
+    service: String,
+    #[serde(rename = "Resource")]
+    resource: String,
+    #[serde(rename = "Type")]
+    span_type: String,
+    #[serde(rename = "HTTPStatusCode")]
+    http_status_code: u32,
+    #[serde(rename = "Synthetics")]
+    synthetics: bool,
+    #[serde(rename = "Hits")]
+    hits: u64,
+    #[serde(rename = "TopLevelHits")]
+    top_level_hits: u64,
+    #[serde(rename = "Errors")]
+    errors: u64,
+    #[serde(rename = "Duration")]
+    duration: u64,
+    #[serde(rename = "OkSummary")]
+    ok_summary: Vec<u8>,
+    #[serde(rename = "ErrorSummary")]
+    error_summary: Vec<u8>,
+}
+
+/// The `/v0.6/stats` wire format's top-level payload, matching the agent's
+/// `ClientStatsPayload`. Field names must match the agent's decoder exactly,
+/// see [`ClientStatsBucket`].
+#[derive(Serialize)]
+struct ClientStatsPayload {
+    #[serde(rename = "Hostname")]
+    hostname: String,
+    #[serde(rename = "Env")]
+    env: String,
+    #[serde(rename = "Version")]
+    version: String,
+    #[serde(rename = "Stats")]
+    stats: Vec<ClientStatsGroup>,
+}
+
+/// One aggregation window, matching the agent's `ClientStatsBucket` (not to
+/// be confused with our own `ClientStatsBucket` above, which is one level
+/// down and matches the agent's `ClientGroupedStats`). Field names must
+/// match the agent's decoder exactly.
+#[derive(Serialize)]
+struct ClientStatsGroup {
+    #[serde(rename = "Start")]
+    start: u64,
+    #[serde(rename = "Duration")]
+    duration: u64,
+    #[serde(rename = "Stats")]
+    stats: Vec<ClientStatsBucket>,
+}
+
+fn hostname() -> String {
+    std::env::var("HOSTNAME").unwrap_or_else(|_| "unknown".to_string())
+}
+
+fn build_payload(
+    config: &TelemetryConfig,
+    bucket_secs: u64,
+    window_start: u64,
+    window: HashMap<BucketKey, BucketStats>,
+) -> ClientStatsPayload {
+    let stats = window
+        .into_iter()
+        .map(|(key, stats)| ClientStatsBucket {
+            name: key.name,
+            service: key.service,
+            resource: key.resource,
+            span_type: key.span_type,
+            http_status_code: key.http_status_code,
+            synthetics: key.synthetics,
+            hits: stats.hits,
+            top_level_hits: stats.top_level_hits,
+            errors: stats.errors,
+            duration: stats.duration_nanos,
+            ok_summary: stats.duration_sketch.encode_to_vec(),
+            error_summary: stats.error_duration_sketch.encode_to_vec(),
+        })
+        .collect();
+
+    ClientStatsPayload {
+        hostname: hostname(),
+        env: config.dd_env.clone(),
+        version: config.dd_version.clone(),
+        stats: vec![ClientStatsGroup {
+            start: window_start * 1_000_000_000,
+            duration: bucket_secs * 1_000_000_000,
+            stats,
+        }],
+    }
+}
+
+fn flush_once(config: &TelemetryConfig, aggregator: &Arc<Mutex<Aggregator>>, bucket_secs: u64) {
+    let current_window = window_start_secs(bucket_secs);
+
+    let closed: Vec<(u64, HashMap<BucketKey, BucketStats>)> = {
+        let mut aggregator = aggregator.lock().unwrap();
+        let closed_keys: Vec<u64> = aggregator
+            .windows
+            .keys()
+            .copied()
+            .filter(|start| *start < current_window)
+            .collect();
+        closed_keys
+            .into_iter()
+            .filter_map(|start| aggregator.windows.remove(&start).map(|w| (start, w)))
+            .collect()
+    };
+
+    for (window_start, window) in closed {
+        let payload = build_payload(config, bucket_secs, window_start, window);
+
+        let body = match rmp_serde::to_vec_named(&payload) {
+            Ok(body) => body,
+            Err(e) => {
+                eprintln!("⚠️  Failed to encode trace stats payload: {}", e);
+                continue;
+            }
+        };
+
+        let url = format!("{}/v0.6/stats", config.dd_trace_agent_url.trim_end_matches('/'));
+        match reqwest::blocking::Client::new()
+            .post(&url)
+            .header("Content-Type", "application/msgpack")
+            .body(body)
+            .send()
+        {
+            Ok(_) => health::record(HealthMetric::Count(health::names::SEND_TRACES, 1)),
+            Err(e) => {
+                health::record(HealthMetric::Count(health::names::SEND_TRACES_ERRORS, 1));
+                eprintln!("⚠️  Failed to send trace stats to {}: {}", url, e);
+            }
+        }
+    }
+}
+
+/// Install the trace stats layer and spawn its background flush thread.
+///
+/// Returns the `Layer` to add to the Datadog subscriber stack. The flush
+/// thread runs for the lifetime of the process. `Telemetry::shutdown` does
+/// not stop it, and windows still open at exit are not flushed.
+pub fn init_trace_stats<S>(config: &TelemetryConfig) -> Box<dyn Layer<S> + Send + Sync>
+where
+    S: tracing::Subscriber + for<'a> LookupSpan<'a>,
+{
+    let bucket_secs = config.trace_stats_bucket_secs.max(1);
+    let layer = TraceStatsLayer::new(config.dd_service.clone(), bucket_secs);
+    let aggregator = Arc::clone(&layer.aggregator);
+    let config = config.clone();
+
+    thread::spawn(move || loop {
+        thread::sleep(Duration::from_secs(bucket_secs));
+        flush_once(&config, &aggregator, bucket_secs);
+    });
+
+    Box::new(layer)
+}
@@ -1,9 +1,88 @@
-use crate::{TelemetryConfig, TelemetryError};
+use crate::health::{self, HealthMetric};
+use crate::{LogRotation, OtlpProtocol, TelemetryConfig, TelemetryError, TraceExporter};
 use opentelemetry::trace::TracerProvider;
+use std::sync::Once;
 use tracing::Metadata;
+use tracing_appender::non_blocking::WorkerGuard;
+use tracing_appender::rolling::{RollingFileAppender, Rotation};
 use tracing_subscriber::layer::{Context as LayerContext, Filter, Layer, SubscriberExt};
 use tracing_subscriber::registry::LookupSpan;
-use tracing_subscriber::{EnvFilter, Registry};
+use tracing_subscriber::{reload, EnvFilter, Registry};
+
+/// Handle to live-reload the fmt/console layer's `EnvFilter` via
+/// [`crate::Telemetry::set_log_filter`].
+pub type LogFilterHandle = reload::Handle<EnvFilter, Registry>;
+
+static HEALTH_ERROR_HANDLER: Once = Once::new();
+
+/// Report every error passed to OpenTelemetry's global error handler (e.g.
+/// a span batch the exporter couldn't deliver) as a
+/// `sideways.send.traces.errors` health metric. Installed at most once per
+/// process.
+fn install_health_error_handler() {
+    HEALTH_ERROR_HANDLER.call_once(|| {
+        let _ = opentelemetry::global::set_error_handler(|_err| {
+            health::record(HealthMetric::Count(health::names::SEND_TRACES_ERRORS, 1));
+        });
+    });
+}
+
+impl From<LogRotation> for Rotation {
+    fn from(rotation: LogRotation) -> Self {
+        match rotation {
+            LogRotation::Minutely => Rotation::MINUTELY,
+            LogRotation::Hourly => Rotation::HOURLY,
+            LogRotation::Daily => Rotation::DAILY,
+            LogRotation::Never => Rotation::NEVER,
+        }
+    }
+}
+
+/// Build the rolling file log layer described by `config`, if `log_dir` is set.
+///
+/// The log file name is composed by the `tracing-appender` crate as
+/// `prefix.date.suffix`, eliding the separating dots when `prefix` or
+/// `suffix` is empty, so a non-empty `log_filename_suffix` of `"log"` keeps
+/// editors that key off the `.log` extension happy.
+///
+/// Filtered by the same `RUST_LOG`-derived `EnvFilter` as the console layer,
+/// so the file doesn't receive an unfiltered firehose (and raise the
+/// registry's global max-level hint) when the console is scoped down. Unlike
+/// the console layer's, this filter isn't reloadable via
+/// `Telemetry::set_log_filter` - it's fixed at startup.
+fn build_file_layer<S>(config: &TelemetryConfig) -> Option<(Box<dyn Layer<S> + Send + Sync>, WorkerGuard)>
+where
+    S: tracing::Subscriber + for<'a> LookupSpan<'a>,
+{
+    let log_dir = config.log_dir.as_ref()?;
+
+    let mut builder = RollingFileAppender::builder().rotation(config.log_rotation.into());
+
+    if !config.log_filename_prefix.is_empty() {
+        builder = builder.filename_prefix(&config.log_filename_prefix);
+    }
+    if !config.log_filename_suffix.is_empty() {
+        builder = builder.filename_suffix(&config.log_filename_suffix);
+    }
+
+    let appender = match builder.build(log_dir) {
+        Ok(appender) => appender,
+        Err(e) => {
+            eprintln!("⚠️  Failed to initialize file logging in {}: {}", log_dir, e);
+            return None;
+        }
+    };
+
+    let (writer, guard) = tracing_appender::non_blocking(appender);
+
+    let file_layer = tracing_subscriber::fmt::layer()
+        .with_ansi(false)
+        .with_writer(writer)
+        .with_filter(get_env_filter(config))
+        .boxed();
+
+    Some((file_layer, guard))
+}
 
 /// Get an EnvFilter from configuration.
 fn get_env_filter(config: &TelemetryConfig) -> EnvFilter {
@@ -21,20 +100,62 @@ fn get_env_filter(config: &TelemetryConfig) -> EnvFilter {
 }
 
 /// Initialize console-only logging without Datadog telemetry.
-pub fn init_console_logging(config: &TelemetryConfig) {
-    let env_filter = get_env_filter(config);
-
+///
+/// Returns the `WorkerGuard` for the rolling file writer when
+/// `config.log_dir` is set (this must be kept alive for the duration of the
+/// program or the background writer thread shuts down early), plus a
+/// [`LogFilterHandle`] that lets the console layer's filter be changed at
+/// runtime via `Telemetry::set_log_filter`.
+pub fn init_console_logging(config: &TelemetryConfig) -> (Option<WorkerGuard>, LogFilterHandle) {
     let subscriber = Registry::default();
-    let console_layer = tracing_subscriber::fmt::layer()
-        .with_ansi(false)
-        .with_filter(env_filter);
+    let layers = build_common_layers(config, false);
 
-    let layered_subscriber = subscriber.with(console_layer);
+    // No tracer is running without Datadog/OTLP, so trace stats have
+    // nothing to bucket; the console and file layers still apply.
+    let layered_subscriber = subscriber.with(layers.console_layer).with(layers.file_layer);
 
     match tracing::subscriber::set_global_default(layered_subscriber) {
         Ok(_) => eprintln!("✅ Console logging initialized"),
         Err(e) => eprintln!("❌ Failed to initialize console logging: {}", e),
     }
+
+    (layers.log_guard, layers.filter_handle)
+}
+
+/// Layers shared by every tracing backend: the reloadable console layer, the
+/// optional rolling file layer, and the optional trace stats aggregator.
+struct CommonLayers {
+    console_layer: Box<dyn Layer<Registry> + Send + Sync>,
+    file_layer: Option<Box<dyn Layer<Registry> + Send + Sync>>,
+    trace_stats_layer: Option<Box<dyn Layer<Registry> + Send + Sync>>,
+    log_guard: Option<WorkerGuard>,
+    filter_handle: LogFilterHandle,
+}
+
+fn build_common_layers(config: &TelemetryConfig, with_trace_stats: bool) -> CommonLayers {
+    let env_filter = get_env_filter(config);
+    let (reloadable_filter, filter_handle) = reload::Layer::new(env_filter);
+
+    let console_layer = tracing_subscriber::fmt::layer()
+        .with_ansi(false)
+        .with_filter(reloadable_filter)
+        .boxed();
+
+    let (file_layer, log_guard) = match build_file_layer::<Registry>(config) {
+        Some((layer, guard)) => (Some(layer), Some(guard)),
+        None => (None, None),
+    };
+
+    let trace_stats_layer = (with_trace_stats && config.trace_stats_enabled)
+        .then(|| crate::stats::init_trace_stats::<Registry>(config));
+
+    CommonLayers {
+        console_layer,
+        file_layer,
+        trace_stats_layer,
+        log_guard,
+        filter_handle,
+    }
 }
 
 /// Custom filter to exclude health check spans from tracing
@@ -70,11 +191,22 @@ where
 
 /// Initialize Datadog tracing using dd-trace-rs.
 ///
-/// Returns Ok with provider if Datadog is available, or Err if initialization fails.
+/// Returns Ok with the provider, the `WorkerGuard` for the rolling file
+/// writer when `config.log_dir` is set (which must be kept alive for the
+/// duration of the program), and a [`LogFilterHandle`] that lets the
+/// console layer's filter be changed at runtime via
+/// `Telemetry::set_log_filter`. Returns Err if initialization fails.
 pub fn init_datadog(
     config: &TelemetryConfig,
-) -> Result<opentelemetry_sdk::trace::SdkTracerProvider, TelemetryError> {
-    let env_filter = get_env_filter(config);
+) -> Result<
+    (
+        opentelemetry_sdk::trace::SdkTracerProvider,
+        Option<WorkerGuard>,
+        LogFilterHandle,
+    ),
+    TelemetryError,
+> {
+    install_health_error_handler();
 
     let subscriber = Registry::default();
 
@@ -87,21 +219,92 @@ pub fn init_datadog(
         )
         .init();
 
-    let console_layer = tracing_subscriber::fmt::layer()
-        .with_ansi(false)
-        .with_filter(env_filter);
-
     let telemetry_layer = tracing_opentelemetry::layer()
         .with_tracer(tracer_provider.tracer(config.dd_service.clone()))
         .with_filter(HealthCheckFilter)
         .with_filter(tracing_subscriber::filter::LevelFilter::INFO);
 
-    let layered_subscriber = subscriber.with(console_layer).with(telemetry_layer);
+    let layers = build_common_layers(config, true);
+
+    let layered_subscriber = subscriber
+        .with(layers.console_layer)
+        .with(telemetry_layer)
+        .with(layers.trace_stats_layer)
+        .with(layers.file_layer);
 
     tracing::subscriber::set_global_default(layered_subscriber)
         .map_err(|e| TelemetryError::SubscriberInit(e.to_string()))?;
 
     tracing::info!("🦀 Datadog tracing initialized successfully");
 
-    Ok(tracer_provider)
+    Ok((tracer_provider, layers.log_guard, layers.filter_handle))
+}
+
+/// Initialize tracing with spans exported over OTLP to an arbitrary
+/// OpenTelemetry collector, instead of a local Datadog trace agent.
+///
+/// Returns Ok with the provider, the `WorkerGuard` for the rolling file
+/// writer when `config.log_dir` is set (which must be kept alive for the
+/// duration of the program), and a [`LogFilterHandle`] that lets the
+/// console layer's filter be changed at runtime via
+/// `Telemetry::set_log_filter`. Returns Err if initialization fails.
+pub fn init_otlp(
+    config: &TelemetryConfig,
+) -> Result<
+    (
+        opentelemetry_sdk::trace::SdkTracerProvider,
+        Option<WorkerGuard>,
+        LogFilterHandle,
+    ),
+    TelemetryError,
+> {
+    install_health_error_handler();
+
+    let TraceExporter::Otlp { protocol, endpoint } = &config.trace_exporter else {
+        unreachable!("init_otlp called with a non-OTLP trace_exporter");
+    };
+
+    let subscriber = Registry::default();
+
+    let mut exporter_builder = opentelemetry_otlp::SpanExporter::builder();
+    exporter_builder = match protocol {
+        OtlpProtocol::Grpc => exporter_builder.with_tonic(),
+        OtlpProtocol::Http => exporter_builder.with_http(),
+    };
+    if let Some(endpoint) = endpoint {
+        exporter_builder = exporter_builder.with_endpoint(endpoint.clone());
+    }
+
+    let exporter = exporter_builder
+        .build()
+        .map_err(|e| TelemetryError::SubscriberInit(format!("failed to build OTLP exporter: {}", e)))?;
+
+    let resource = opentelemetry_sdk::Resource::builder()
+        .with_service_name(config.dd_service.clone())
+        .build();
+
+    let tracer_provider = opentelemetry_sdk::trace::SdkTracerProvider::builder()
+        .with_batch_exporter(exporter)
+        .with_resource(resource)
+        .build();
+
+    let telemetry_layer = tracing_opentelemetry::layer()
+        .with_tracer(tracer_provider.tracer(config.dd_service.clone()))
+        .with_filter(HealthCheckFilter)
+        .with_filter(tracing_subscriber::filter::LevelFilter::INFO);
+
+    let layers = build_common_layers(config, true);
+
+    let layered_subscriber = subscriber
+        .with(layers.console_layer)
+        .with(telemetry_layer)
+        .with(layers.trace_stats_layer)
+        .with(layers.file_layer);
+
+    tracing::subscriber::set_global_default(layered_subscriber)
+        .map_err(|e| TelemetryError::SubscriberInit(e.to_string()))?;
+
+    tracing::info!("🦀 OTLP tracing initialized successfully");
+
+    Ok((tracer_provider, layers.log_guard, layers.filter_handle))
 }